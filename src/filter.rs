@@ -0,0 +1,243 @@
+//! A reusable in-process matcher API: ingest a candidate corpus once, then
+//! score it against queries either synchronously ([`Filter::filter`]) or as
+//! an incrementally-ranked async stream ([`Filter::filter_stream`]).
+use crate::matcher::{Candidate, Matcher, ScoredCandidate};
+
+/// A single ranked match returned by [`Filter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub score: i32,
+    pub line: String,
+}
+
+impl From<ScoredCandidate> for Match {
+    fn from(scored: ScoredCandidate) -> Self {
+        Self {
+            score: scored.score,
+            line: scored.line,
+        }
+    }
+}
+
+/// Ingests a candidate corpus once (parsing/extracting fields up front),
+/// then scores it against repeated queries.
+pub struct Filter {
+    matcher: Matcher,
+}
+
+impl Filter {
+    /// Shards `candidates` across `jobs` worker threads, defaulting to the
+    /// available parallelism when `jobs` is `None`.
+    pub fn new(candidates: Vec<Candidate>, jobs: Option<usize>) -> Self {
+        Self {
+            matcher: Matcher::new(candidates, jobs),
+        }
+    }
+
+    /// Scores the corpus against `pattern` and returns up to `limit`
+    /// matches, highest score first.
+    pub fn filter(&self, pattern: &str, limit: Option<usize>) -> Vec<Match> {
+        self.matcher
+            .search(pattern, limit)
+            .into_iter()
+            .map(Match::from)
+            .collect()
+    }
+
+    /// Scores the corpus against `pattern`, yielding increasingly complete
+    /// ranked snapshots as workers finish instead of waiting for the whole
+    /// scan to land before returning anything.
+    ///
+    /// Calling this again supersedes any scan still in flight from a
+    /// previous call: `Matcher::dispatch` bumps a shared epoch, so a worker
+    /// skips a still-queued job from an older call outright, and bails out
+    /// of a job it's already scoring the next time it checks the epoch.
+    /// This mirrors the "only keep the latest query" behavior the
+    /// interactive CLI gets from `rx.try_iter().last()`. A scan already in
+    /// progress only re-checks the epoch every so many candidates, so a
+    /// superseded scan can still do a bounded amount of wasted work before
+    /// it notices and stops. Dropping the stream by itself does not stop
+    /// its scan; only a later `filter_stream` or `filter` call does.
+    #[cfg(feature = "async")]
+    pub fn filter_stream(&self, pattern: &str, limit: Option<usize>) -> stream::FilterStream {
+        stream::FilterStream::new(self.matcher.dispatch(pattern, limit), limit)
+    }
+}
+
+#[cfg(feature = "async")]
+pub use stream::FilterStream;
+
+#[cfg(feature = "async")]
+mod stream {
+    use std::pin::Pin;
+    use std::sync::mpsc::Receiver;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll, Waker};
+    use std::thread;
+
+    use futures_core::Stream;
+
+    use crate::matcher::{compare, ScoredCandidate};
+
+    use super::Match;
+
+    struct SharedState {
+        merged: Vec<ScoredCandidate>,
+        limit: Option<usize>,
+        remaining: usize,
+        ready: bool,
+        done: bool,
+        exhausted: bool,
+        waker: Option<Waker>,
+    }
+
+    /// An async stream of increasingly complete ranked snapshots, produced
+    /// by [`super::Filter::filter_stream`]. Each item is the full merged
+    /// ranking seen so far, truncated to the requested limit; the last item
+    /// before the stream ends reflects every worker's result.
+    pub struct FilterStream {
+        shared: Arc<Mutex<SharedState>>,
+    }
+
+    impl FilterStream {
+        pub(super) fn new(
+            replies: Vec<Receiver<Vec<ScoredCandidate>>>,
+            limit: Option<usize>,
+        ) -> Self {
+            let shared = Arc::new(Mutex::new(SharedState {
+                merged: Vec::new(),
+                limit,
+                remaining: replies.len(),
+                ready: false,
+                done: replies.is_empty(),
+                exhausted: false,
+                waker: None,
+            }));
+
+            for reply_rx in replies {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || {
+                    let partial = reply_rx.recv().unwrap_or_default();
+                    let mut state = shared.lock().unwrap();
+                    state.merged.extend(partial);
+                    state.merged.sort_unstable_by(compare);
+                    if let Some(limit) = state.limit {
+                        state.merged.truncate(limit);
+                    }
+                    state.remaining -= 1;
+                    state.done = state.remaining == 0;
+                    state.ready = true;
+                    if let Some(waker) = state.waker.take() {
+                        waker.wake();
+                    }
+                });
+            }
+
+            Self { shared }
+        }
+    }
+
+    impl Stream for FilterStream {
+        type Item = Vec<Match>;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            let mut state = self.shared.lock().unwrap();
+
+            if state.exhausted {
+                return Poll::Ready(None);
+            }
+            if state.ready {
+                state.ready = false;
+                let batch = state.merged.iter().cloned().map(Match::from).collect();
+                state.exhausted = state.done;
+                return Poll::Ready(Some(batch));
+            }
+            if state.done {
+                state.exhausted = true;
+                return Poll::Ready(None);
+            }
+
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::Candidate;
+
+    include!("test_support.rs");
+
+    #[test]
+    fn filter_returns_ranked_matches_up_to_limit() {
+        let filter = Filter::new(corpus_candidates(), Some(2));
+
+        let matched = filter.filter("abc", Some(2));
+
+        assert_eq!(matched.len(), 2);
+        assert!(matched
+            .windows(2)
+            .all(|pair| pair[0].score >= pair[1].score));
+    }
+
+    #[cfg(feature = "async")]
+    mod stream_tests {
+        use std::pin::Pin;
+        use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+        use super::corpus_candidates;
+        use crate::filter::{Filter, FilterStream, Match};
+
+        fn noop_waker() -> Waker {
+            fn clone(_: *const ()) -> RawWaker {
+                raw_waker()
+            }
+            fn noop(_: *const ()) {}
+            fn raw_waker() -> RawWaker {
+                static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+                RawWaker::new(std::ptr::null(), &VTABLE)
+            }
+            unsafe { Waker::from_raw(raw_waker()) }
+        }
+
+        /// Busy-polls `stream` to completion. Fine for tests: there's no
+        /// async runtime dependency in this crate to drive a real one.
+        fn block_on_all(mut stream: FilterStream) -> Vec<Vec<Match>> {
+            let waker = noop_waker();
+            let mut cx = Context::from_waker(&waker);
+            let mut items = Vec::new();
+            loop {
+                match Pin::new(&mut stream).poll_next(&mut cx) {
+                    Poll::Ready(Some(batch)) => items.push(batch),
+                    Poll::Ready(None) => break,
+                    Poll::Pending => std::thread::yield_now(),
+                }
+            }
+            items
+        }
+
+        #[test]
+        fn filter_stream_yields_the_final_ranking_once_exhausted() {
+            let filter = Filter::new(corpus_candidates(), Some(2));
+
+            let items = block_on_all(filter.filter_stream("abc", None));
+
+            let last = items.last().expect("at least one snapshot");
+            let lines: Vec<&str> = last.iter().map(|m| m.line.as_str()).collect();
+            assert_eq!(lines, vec!["abc", "xabcx", "a_b_c"]);
+        }
+
+        #[test]
+        fn filter_stream_respects_the_limit() {
+            let filter = Filter::new(corpus_candidates(), Some(2));
+
+            let items = block_on_all(filter.filter_stream("abc", Some(1)));
+
+            let last = items.last().expect("at least one snapshot");
+            assert_eq!(last.len(), 1);
+            assert_eq!(last[0].line, "abc");
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::slice;
+use core::slice;
 
 use crate::fzf_sys as ffi;
 
@@ -36,7 +36,7 @@ pub struct Pattern(*mut ffi::fzf_pattern_t);
 
 impl Pattern {
     #[inline]
-    pub fn new(pattern: &str, case_mode: CaseMode, fuzzy: bool) -> Self {
+    pub fn new(pattern: &[u8], case_mode: CaseMode, fuzzy: bool) -> Self {
         let pattern_obj = unsafe {
             ffi::fzf_parse_pattern(
                 case_mode as u32,
@@ -87,12 +87,12 @@ impl Drop for Positions {
 }
 
 #[inline]
-pub fn get_score(line: &str, pattern: &Pattern, slab: &Slab) -> i32 {
+pub fn get_score(line: &[u8], pattern: &Pattern, slab: &Slab) -> i32 {
     unsafe { ffi::fzf_get_score(line.as_ptr() as *const i8, line.len(), pattern.0, slab.0) }
 }
 
 #[inline]
-pub fn get_pos(line: &str, pattern: &Pattern, slab: &Slab) -> Positions {
+pub fn get_pos(line: &[u8], pattern: &Pattern, slab: &Slab) -> Positions {
     let positions = unsafe {
         ffi::fzf_get_positions(line.as_ptr() as *const i8, line.len(), pattern.0, slab.0)
     };
@@ -0,0 +1,30 @@
+//! Safe wrapper around the `fzf` fuzzy-matching algorithm (`src/fzf-native`).
+//!
+//! This crate is `no_std` (plus `alloc`) so the matcher can be embedded in
+//! GUI/TUI front-ends that don't want the newline-delimited stdin protocol
+//! implemented by the `fzf-filter` binary. The binary itself is built behind
+//! the default-on `cli` feature; see `src/main.rs`.
+#![no_std]
+
+extern crate alloc;
+
+#[allow(
+    non_upper_case_globals,
+    non_camel_case_types,
+    non_snake_case,
+    dead_code
+)]
+mod fzf_sys {
+    include!(concat!(env!("OUT_DIR"), "/fzf_sys.rs"));
+}
+
+pub mod fzf;
+
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "std")]
+pub mod matcher;
+
+#[cfg(feature = "std")]
+pub mod filter;
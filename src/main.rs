@@ -1,5 +1,5 @@
+//! Subprocess CLI front-end for `fzf_filter`, built behind the `cli` feature.
 use std::borrow::Cow;
-use std::cmp::Reverse;
 use std::env::{self, ArgsOs};
 use std::ffi::OsString;
 use std::io::{self, Write as _};
@@ -9,6 +9,7 @@ use std::sync::mpsc;
 use std::thread;
 
 use fzf_filter::fzf;
+use fzf_filter::matcher::{self, Candidate, Matcher, ScoredCandidate};
 
 const HELP: &'static str = "\
 USAGE:
@@ -20,7 +21,11 @@ OPTIONS:
                               (default: whole line)
   -p, --field-partitions NUM  a maximum number of partitions of the field
   -d, --field-delimiter CHAR  a field delimiter character
-                              (default: \\t)";
+                              (default: \\t)
+  -j, --jobs NUM              a number of worker threads used to score lines
+                              (default: available parallelism)
+      --with-positions        emit matched byte offsets after the
+                              sequence number, as \"SEQ off1,off2,... LINE\"";
 
 fn main() -> anyhow::Result<ExitCode> {
     let mode = Args::parse(env::args_os());
@@ -56,25 +61,49 @@ fn run(args: Args) -> anyhow::Result<ExitCode> {
                 partitions,
                 delimiter: args.field_delimiter,
             };
-            run_loop(output_content, args.limit_items, extractor);
+            run_loop(
+                output_content,
+                args.limit_items,
+                args.jobs,
+                args.with_positions,
+                extractor,
+            );
         }
         (Some(index), None) => {
             let extractor = IndexExtractor {
                 index,
                 delimiter: args.field_delimiter,
             };
-            run_loop(output_content, args.limit_items, extractor);
+            run_loop(
+                output_content,
+                args.limit_items,
+                args.jobs,
+                args.with_positions,
+                extractor,
+            );
         }
         _ => {
             let extractor = ThroughExtractor;
-            run_loop(output_content, args.limit_items, extractor);
+            run_loop(
+                output_content,
+                args.limit_items,
+                args.jobs,
+                args.with_positions,
+                extractor,
+            );
         }
     }
 
     return Ok(ExitCode::SUCCESS);
 }
 
-fn run_loop(output_content: Cow<str>, limit_items: Option<usize>, extractor: impl Extractor) {
+fn run_loop(
+    output_content: Cow<str>,
+    limit_items: Option<usize>,
+    jobs: Option<usize>,
+    with_positions: bool,
+    extractor: impl Extractor,
+) {
     let (tx, rx) = mpsc::channel::<String>();
 
     thread::spawn(move || {
@@ -88,7 +117,26 @@ fn run_loop(output_content: Cow<str>, limit_items: Option<usize>, extractor: imp
         }
     });
 
-    let slab = fzf::Slab::default();
+    let candidates = output_content
+        .lines()
+        .filter_map(|line| {
+            let content = extractor.extract(line)?;
+            let field_offset = content.as_ptr() as usize - line.as_ptr() as usize;
+            Some(Candidate {
+                content: content.to_owned(),
+                line: line.to_owned(),
+                field_offset,
+            })
+        })
+        .collect();
+    let matcher = Matcher::new(candidates, jobs);
+
+    // Snapshots of (pattern, full matched set) for patterns typed so far,
+    // shortest at the bottom. Lets a query that extends the previous one
+    // rescore only the survivors of that previous query instead of the
+    // whole corpus; backspacing pops back to an earlier, still-valid
+    // snapshot rather than forcing a full rescan.
+    let mut cache: Vec<(String, Vec<ScoredCandidate>)> = Vec::new();
 
     while let Ok(line) = rx.recv() {
         let line = rx.try_iter().last().unwrap_or(line);
@@ -96,6 +144,14 @@ fn run_loop(output_content: Cow<str>, limit_items: Option<usize>, extractor: imp
             continue;
         };
         if pattern.is_empty() {
+            cache.clear();
+            // With `--with-positions`, keep an (empty) offsets field so every
+            // line has the same "SEQ OFFSETS LINE" shape.
+            let sequence = if with_positions {
+                format!("{} ", sequence)
+            } else {
+                sequence.to_owned()
+            };
             if let Some(limit_items) = limit_items {
                 for line in output_content.lines().take(limit_items) {
                     println!("{} {}", sequence, line)
@@ -106,30 +162,27 @@ fn run_loop(output_content: Cow<str>, limit_items: Option<usize>, extractor: imp
                 }
             }
         } else {
-            let pattern = fzf::Pattern::new(pattern, fzf::CaseMode::Smart, true);
-            let mut matched_lines = vec![];
-
-            for line in output_content.lines() {
-                if let Some(content) = extractor.extract(line) {
-                    let score = fzf::get_score(content, &pattern, &slab);
-                    if score > 0 {
-                        matched_lines.push((Reverse(score), line));
-                    }
+            let matched = rescore(&mut cache, &matcher, pattern);
+            let matched = match limit_items {
+                Some(limit_items) if matched.len() > limit_items => &matched[..limit_items],
+                _ => &matched[..],
+            };
+            if with_positions {
+                let slab = fzf::Slab::default();
+                let pattern = fzf::Pattern::new(pattern.as_bytes(), fzf::CaseMode::Smart, true);
+                for scored in matched {
+                    let positions = fzf::get_pos(scored.content.as_bytes(), &pattern, &slab);
+                    let offsets = translate_offsets(positions.as_slice(), scored.field_offset)
+                        .iter()
+                        .map(|offset| offset.to_string())
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    println!("{} {} {}", sequence, offsets, scored.line);
                 }
-            }
-
-            let matched_lines = match limit_items {
-                Some(limit_items) if matched_lines.len() > limit_items => {
-                    let (partial_lines, _, _) = matched_lines.select_nth_unstable(limit_items);
-                    partial_lines
+            } else {
+                for scored in matched {
+                    println!("{} {}", sequence, scored.line);
                 }
-                _ => matched_lines.as_mut_slice(),
-            };
-
-            matched_lines.sort_unstable();
-
-            for (_, line) in matched_lines {
-                println!("{} {}", sequence, line);
             }
         }
 
@@ -137,6 +190,67 @@ fn run_loop(output_content: Cow<str>, limit_items: Option<usize>, extractor: imp
     }
 }
 
+/// Returns the full (untruncated) matched set for `pattern`, reusing the
+/// narrowest cached snapshot whose pattern is a prefix of `pattern` instead
+/// of rescoring the whole corpus. The result is pushed onto `cache` for
+/// later reuse, so callers must not truncate it before it is cached.
+fn rescore(
+    cache: &mut Vec<(String, Vec<ScoredCandidate>)>,
+    matcher: &Matcher,
+    pattern: &str,
+) -> Vec<ScoredCandidate> {
+    while matches!(cache.last(), Some((cached, _)) if !pattern.starts_with(cached.as_str())) {
+        cache.pop();
+    }
+
+    if let Some((cached, matched)) = cache.last() {
+        if cached == pattern {
+            return matched.clone();
+        }
+    }
+
+    let matched = match cache.last() {
+        Some((_, matched)) => rescan(matched, pattern),
+        None => matcher.search(pattern, None),
+    };
+
+    cache.push((pattern.to_owned(), matched.clone()));
+    matched
+}
+
+/// Rescans an already-matched set against a longer pattern, sequentially
+/// with a throwaway `Slab` since the surviving set is expected to be much
+/// smaller than the full corpus.
+fn rescan(candidates: &[ScoredCandidate], pattern: &str) -> Vec<ScoredCandidate> {
+    let slab = fzf::Slab::default();
+    let pattern = fzf::Pattern::new(pattern.as_bytes(), fzf::CaseMode::Smart, true);
+
+    let mut matched: Vec<ScoredCandidate> = candidates
+        .iter()
+        .filter_map(|candidate| {
+            let score = fzf::get_score(candidate.content.as_bytes(), &pattern, &slab);
+            (score > 0).then(|| ScoredCandidate {
+                score,
+                content: candidate.content.clone(),
+                line: candidate.line.clone(),
+                field_offset: candidate.field_offset,
+            })
+        })
+        .collect();
+    matched.sort_unstable_by(matcher::compare);
+    matched
+}
+
+/// Shifts `positions` (byte offsets within the matched, possibly
+/// field-extracted, content) by `field_offset` to get offsets within the
+/// original output line, for `--with-positions`.
+fn translate_offsets(positions: &[u32], field_offset: usize) -> Vec<usize> {
+    positions
+        .iter()
+        .map(|&offset| offset as usize + field_offset)
+        .collect()
+}
+
 #[derive(Debug)]
 struct Args {
     command: OsString,
@@ -145,6 +259,8 @@ struct Args {
     field_index: Option<usize>,
     field_partitions: Option<usize>,
     limit_items: Option<usize>,
+    jobs: Option<usize>,
+    with_positions: bool,
 }
 
 impl Args {
@@ -171,6 +287,8 @@ impl Args {
             field_index: pico_args.opt_value_from_str(["-f", "--field-index"])?,
             field_partitions: pico_args.opt_value_from_str(["-p", "--field-partitions"])?,
             limit_items: pico_args.opt_value_from_str(["-l", "--limit-items"])?,
+            jobs: pico_args.opt_value_from_str(["-j", "--jobs"])?,
+            with_positions: pico_args.contains("--with-positions"),
         }))
     }
 }
@@ -209,3 +327,130 @@ impl Extractor for ThroughExtractor {
         return Some(s);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    include!("test_support.rs");
+
+    fn corpus() -> Matcher {
+        Matcher::new(corpus_candidates(), Some(2))
+    }
+
+    fn lines(matched: &[ScoredCandidate]) -> Vec<&str> {
+        matched.iter().map(|m| m.line.as_str()).collect()
+    }
+
+    #[test]
+    fn rescore_extending_a_pattern_reuses_the_cached_survivors() {
+        let matcher = corpus();
+        let mut cache = Vec::new();
+
+        rescore(&mut cache, &matcher, "a");
+        let extended = rescore(&mut cache, &matcher, "ab");
+
+        let fresh = matcher.search("ab", None);
+        assert_eq!(lines(&extended), lines(&fresh));
+        // The snapshot for "a" is kept below the new one for "ab".
+        assert_eq!(cache.len(), 2);
+
+        // Drop "xabcx" from the "ab" snapshot: a query extending "ab" can
+        // only miss it if it rescans that snapshot instead of the corpus.
+        cache[1].1.retain(|m| m.line != "xabcx");
+        let narrowed = rescore(&mut cache, &matcher, "abc");
+        assert!(!lines(&narrowed).contains(&"xabcx"));
+    }
+
+    #[test]
+    fn rescore_backspacing_to_a_divergent_pattern_pops_the_stale_cache() {
+        let matcher = corpus();
+        let mut cache = Vec::new();
+
+        rescore(&mut cache, &matcher, "a");
+        rescore(&mut cache, &matcher, "ab");
+        let diverged = rescore(&mut cache, &matcher, "ax");
+
+        let fresh = matcher.search("ax", None);
+        assert_eq!(lines(&diverged), lines(&fresh));
+        // "ax" isn't an extension of "ab", so that entry (and anything
+        // above it) must have been popped before rescanning from "a".
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache[0].0, "a");
+        assert_eq!(cache[1].0, "ax");
+    }
+
+    #[test]
+    fn rescore_an_unrelated_pattern_discards_the_whole_cache() {
+        let matcher = corpus();
+        let mut cache = Vec::new();
+
+        rescore(&mut cache, &matcher, "a");
+        rescore(&mut cache, &matcher, "ab");
+        let unrelated = rescore(&mut cache, &matcher, "xyz");
+
+        let fresh = matcher.search("xyz", None);
+        assert_eq!(lines(&unrelated), lines(&fresh));
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache[0].0, "xyz");
+    }
+
+    #[test]
+    fn rescore_caches_the_untruncated_set_even_when_the_caller_will_truncate() {
+        let matcher = corpus();
+        let mut cache = Vec::new();
+
+        let matched = rescore(&mut cache, &matcher, "a");
+        // `rescore` never sees `limit_items`, so neither its return value
+        // nor the entry it pushes onto `cache` may be truncated — that's
+        // the caller's job, applied only to the slice it prints.
+        assert_eq!(matched.len(), cache[0].1.len());
+        assert!(matched.len() > 1);
+    }
+
+    #[test]
+    fn rescan_filters_out_non_matching_candidates_and_rescores_survivors() {
+        let scored = |content: &str| ScoredCandidate {
+            score: 0,
+            content: content.to_owned(),
+            line: content.to_owned(),
+            field_offset: 0,
+        };
+        let candidates = vec![scored("abc"), scored("xyz"), scored("abd")];
+
+        let matched = rescan(&candidates, "ab");
+
+        assert_eq!(lines(&matched), vec!["abc", "abd"]);
+    }
+
+    #[test]
+    fn translate_offsets_shifts_positions_by_the_field_offset() {
+        assert_eq!(translate_offsets(&[0, 2, 5], 4), vec![4, 6, 9]);
+        assert_eq!(translate_offsets(&[], 4), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn translate_offsets_round_trips_positions_from_an_extracted_field() {
+        // "foo\tbar" with a `-f 1 -d '\t'` extractor matches against the
+        // field "bar", so the positions `fzf::get_pos` reports are relative
+        // to "bar" and must be shifted by the byte offset of "bar" within
+        // the original line to highlight the right columns.
+        let line = "foo\tbar";
+        let extractor = IndexExtractor {
+            index: 1,
+            delimiter: '\t',
+        };
+        let content = extractor.extract(line).unwrap();
+        let field_offset = content.as_ptr() as usize - line.as_ptr() as usize;
+        assert_eq!(field_offset, 4);
+
+        let slab = fzf::Slab::default();
+        let pattern = fzf::Pattern::new(b"bar", fzf::CaseMode::Smart, true);
+        let positions = fzf::get_pos(content.as_bytes(), &pattern, &slab);
+
+        let mut offsets = translate_offsets(positions.as_slice(), field_offset);
+        offsets.sort_unstable();
+
+        assert_eq!(offsets, vec![4, 5, 6]);
+    }
+}
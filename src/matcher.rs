@@ -0,0 +1,309 @@
+//! Parallel scoring across a pool of worker threads.
+//!
+//! `fzf::Slab` is a mutable scratch arena that is not safe to share between
+//! concurrent `fzf::get_score` calls, so each worker owns its own slab and
+//! keeps its shard of candidate lines (and the slab) for as long as the
+//! [`Matcher`] lives, rather than reallocating them on every query.
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::fzf::{get_score, CaseMode, Pattern, Slab};
+
+/// A candidate line, plus the (possibly field-extracted) content it is
+/// matched against.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub content: String,
+    pub line: String,
+    /// Byte offset of `content` within `line`, e.g. where a field extractor
+    /// shifted the match window. Lets match positions reported against
+    /// `content` be translated back to `line`'s coordinates.
+    pub field_offset: usize,
+}
+
+/// A candidate paired with the score it got against the last query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScoredCandidate {
+    pub score: i32,
+    pub content: String,
+    pub line: String,
+    pub field_offset: usize,
+}
+
+impl PartialOrd for ScoredCandidate {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Greater means a better match: higher score, then the lexicographically
+/// smaller line, i.e. the reverse of [`compare`]'s best-first order.
+impl Ord for ScoredCandidate {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.score
+            .cmp(&other.score)
+            .then_with(|| other.line.cmp(&self.line))
+    }
+}
+
+enum Job {
+    Search {
+        pattern: String,
+        limit: Option<usize>,
+        reply: Sender<Vec<ScoredCandidate>>,
+        epoch: u64,
+    },
+    Shutdown,
+}
+
+struct Worker {
+    job_tx: Sender<Job>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        let _ = self.job_tx.send(Job::Shutdown);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Scores candidate lines against a pattern using a pool of worker threads.
+///
+/// The candidate lines are sharded across the workers once, up front; each
+/// worker scores its shard with its own `fzf::Slab` and the results are
+/// merged on the calling thread.
+pub struct Matcher {
+    workers: Vec<Worker>,
+    /// Bumped by every [`Matcher::dispatch`] call. A worker that dequeues a
+    /// job tagged with an epoch older than this skips it instead of scoring
+    /// it, so a burst of queries (e.g. from `Filter::filter_stream`) doesn't
+    /// pile up stale work ahead of the latest one in a worker's queue.
+    epoch: Arc<AtomicU64>,
+}
+
+impl Matcher {
+    /// Shards `candidates` across `jobs` workers, defaulting to the
+    /// available parallelism when `jobs` is `None`.
+    pub fn new(candidates: Vec<Candidate>, jobs: Option<usize>) -> Self {
+        let jobs = jobs
+            .or_else(|| thread::available_parallelism().ok().map(|n| n.get()))
+            .unwrap_or(1)
+            .max(1);
+
+        let epoch = Arc::new(AtomicU64::new(0));
+        let workers = shard(candidates, jobs)
+            .into_iter()
+            .map(|shard| {
+                let (job_tx, job_rx) = mpsc::channel::<Job>();
+                let epoch = Arc::clone(&epoch);
+                let handle = thread::spawn(move || worker_loop(shard, job_rx, epoch));
+                Worker {
+                    job_tx,
+                    handle: Some(handle),
+                }
+            })
+            .collect();
+
+        Self { workers, epoch }
+    }
+
+    /// Scores every shard against `pattern`, keeping at most `limit` lines
+    /// per shard, and merges the per-worker results into a single
+    /// score-descending, ties-broken-by-line-ascending list truncated to
+    /// `limit`. Pass `limit: None` to get every match, e.g. to build a
+    /// cache entry that must not be poisoned by truncation.
+    pub fn search(&self, pattern: &str, limit: Option<usize>) -> Vec<ScoredCandidate> {
+        let mut merged: Vec<ScoredCandidate> = self
+            .dispatch(pattern, limit)
+            .into_iter()
+            .flat_map(|reply_rx| reply_rx.recv().into_iter().flatten())
+            .collect();
+
+        match limit {
+            Some(limit) if merged.len() > limit => {
+                // `compare` sorts best match first, so the elements `select_nth_unstable_by`
+                // places before `limit` are exactly the `limit` best matches.
+                let (partial, _, _) = merged.select_nth_unstable_by(limit, compare);
+                merged = partial.to_vec();
+            }
+            _ => {}
+        }
+        merged.sort_unstable_by(compare);
+        merged
+    }
+
+    /// Sends `pattern` to every worker without waiting for a reply,
+    /// returning one receiver per worker. Used by [`crate::filter::Filter`]
+    /// to merge partial results as workers finish instead of blocking
+    /// until all of them have replied.
+    ///
+    /// Bumps the shared epoch first, so a job still sitting in a worker's
+    /// queue from an earlier `dispatch` call is recognized as stale and
+    /// skipped rather than scored before this one.
+    pub(crate) fn dispatch(
+        &self,
+        pattern: &str,
+        limit: Option<usize>,
+    ) -> Vec<Receiver<Vec<ScoredCandidate>>> {
+        let epoch = self.epoch.fetch_add(1, Ordering::SeqCst) + 1;
+        self.workers
+            .iter()
+            .map(|worker| {
+                let (reply_tx, reply_rx) = mpsc::channel();
+                worker
+                    .job_tx
+                    .send(Job::Search {
+                        pattern: pattern.to_owned(),
+                        limit,
+                        reply: reply_tx,
+                        epoch,
+                    })
+                    .unwrap();
+                reply_rx
+            })
+            .collect()
+    }
+}
+
+/// Score-descending, ties broken by line ascending.
+pub fn compare(a: &ScoredCandidate, b: &ScoredCandidate) -> std::cmp::Ordering {
+    Reverse(a.score)
+        .cmp(&Reverse(b.score))
+        .then_with(|| a.line.cmp(&b.line))
+}
+
+fn shard(candidates: Vec<Candidate>, jobs: usize) -> Vec<Vec<Candidate>> {
+    let mut shards: Vec<Vec<Candidate>> = (0..jobs).map(|_| Vec::new()).collect();
+    for (index, candidate) in candidates.into_iter().enumerate() {
+        shards[index % jobs].push(candidate);
+    }
+    shards
+}
+
+fn worker_loop(candidates: Vec<Candidate>, job_rx: Receiver<Job>, epoch: Arc<AtomicU64>) {
+    let slab = Slab::default();
+
+    while let Ok(job) = job_rx.recv() {
+        let Job::Search {
+            pattern,
+            limit,
+            reply,
+            epoch: job_epoch,
+        } = job
+        else {
+            break;
+        };
+
+        if job_epoch < epoch.load(Ordering::SeqCst) {
+            // A newer query was dispatched while this job was still queued
+            // behind it; skip scoring it and let the reply come back empty.
+            let _ = reply.send(Vec::new());
+            continue;
+        }
+
+        let pattern = Pattern::new(pattern.as_bytes(), CaseMode::Smart, true);
+        let mut heap: BinaryHeap<Reverse<ScoredCandidate>> = BinaryHeap::new();
+
+        // Re-check the epoch periodically (not every candidate, to keep the
+        // atomic load off the hot path) so a long scan also bails out once
+        // it is superseded, rather than only being skipped before it starts.
+        const STALENESS_CHECK_INTERVAL: usize = 1024;
+
+        for (index, candidate) in candidates.iter().enumerate() {
+            if index % STALENESS_CHECK_INTERVAL == 0 && job_epoch < epoch.load(Ordering::SeqCst) {
+                break;
+            }
+
+            let score = get_score(candidate.content.as_bytes(), &pattern, &slab);
+            if score <= 0 {
+                continue;
+            }
+            if let Some(limit) = limit {
+                if heap.len() >= limit {
+                    // Compare on the same (score, reversed line) key `ScoredCandidate`'s
+                    // `Ord` is keyed on, so heap membership ties match the documented
+                    // merge order instead of being decided on score alone.
+                    let evict = matches!(heap.peek(), Some(Reverse(min))
+                        if (score, Reverse(candidate.line.as_str()))
+                            > (min.score, Reverse(min.line.as_str())));
+                    if !evict {
+                        continue;
+                    }
+                    heap.pop();
+                }
+            }
+            heap.push(Reverse(ScoredCandidate {
+                score,
+                content: candidate.content.clone(),
+                line: candidate.line.clone(),
+                field_offset: candidate.field_offset,
+            }));
+        }
+
+        let _ = reply.send(heap.into_iter().map(|Reverse(scored)| scored).collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    include!("test_support.rs");
+
+    #[test]
+    fn search_without_limit_returns_every_match_best_first() {
+        let matcher = Matcher::new(corpus_candidates(), Some(2));
+
+        let matched = matcher.search("abc", None);
+        let lines: Vec<&str> = matched.iter().map(|m| m.line.as_str()).collect();
+
+        assert_eq!(lines, vec!["abc", "xabcx", "a_b_c"]);
+        assert!(matched
+            .windows(2)
+            .all(|pair| pair[0].score >= pair[1].score));
+    }
+
+    #[test]
+    fn search_with_limit_keeps_the_best_matches_not_the_worst() {
+        let matcher = Matcher::new(corpus_candidates(), Some(2));
+
+        let full = matcher.search("abc", None);
+        let limited = matcher.search("abc", Some(2));
+
+        assert_eq!(limited, full[..2].to_vec());
+    }
+
+    #[test]
+    fn worker_loop_skips_a_job_whose_epoch_is_already_stale() {
+        // Stamp a job with an epoch older than the shared counter, as if a
+        // newer dispatch had already superseded it while it was still
+        // sitting in the worker's queue, and pre-queue it (plus a
+        // Shutdown) before the worker ever runs so there's no race: the
+        // skip must happen the instant the job is dequeued, before any
+        // scoring.
+        let epoch = Arc::new(AtomicU64::new(1));
+        let (job_tx, job_rx) = mpsc::channel();
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        job_tx
+            .send(Job::Search {
+                pattern: "abc".to_owned(),
+                limit: None,
+                reply: reply_tx,
+                epoch: 0,
+            })
+            .unwrap();
+        job_tx.send(Job::Shutdown).unwrap();
+
+        worker_loop(corpus_candidates(), job_rx, epoch);
+
+        assert_eq!(reply_rx.recv().unwrap(), Vec::<ScoredCandidate>::new());
+    }
+}
@@ -0,0 +1,23 @@
+// Shared `#[cfg(test)]` candidate fixtures, `include!`-d (not `mod`-ed) into
+// each test module that needs them, since `matcher`/`filter`'s tests live in
+// the library crate while `main`'s live in the binary crate and can't share
+// a `mod`. Relies on the includer already having `Candidate` in scope (e.g.
+// via `use super::*;`), so this has no `use` of its own.
+
+fn candidate(content: &str) -> Candidate {
+    Candidate {
+        content: content.to_owned(),
+        line: content.to_owned(),
+        field_offset: 0,
+    }
+}
+
+fn corpus_candidates() -> Vec<Candidate> {
+    vec![
+        candidate("abc"),
+        candidate("xabcx"),
+        candidate("a_b_c"),
+        candidate("xyz"),
+        candidate("abd"),
+    ]
+}